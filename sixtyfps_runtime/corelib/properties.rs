@@ -0,0 +1,299 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+ The property engine: `Property<T>` and the `InterpolatedPropertyValue` trait that
+ `crate::rtti`'s animation-aware `PropertyInfo` impls require of animatable value types.
+*/
+
+use crate::animations::Instant;
+use crate::items::PropertyAnimation;
+use core::cell::RefCell;
+use core::pin::Pin;
+use core::time::Duration;
+use std::rc::Rc;
+
+/// A value type that can be driven by the property animation system: interpolated between two
+/// values, measured for "distance" (used to preserve velocity across an interrupted
+/// `Transition`, see [`Property::set_animated_binding_for_transition`]), and optionally
+/// combined additively for `crate::rtti::CompositeOp::Add`/`Accumulate` bindings.
+pub trait InterpolatedPropertyValue: Clone + 'static {
+    /// Linearly interpolates `self` towards `target_value`, `t` ranging over `0.0..=1.0`.
+    fn interpolate(&self, target_value: &Self, t: f32) -> Self;
+
+    /// Channel-wise sum-of-squares distance between `self` and `other`, used to estimate an
+    /// in-flight animation's velocity when a `Transition` interrupts it.
+    fn compute_squared_distance(&self, other: &Self) -> f64;
+
+    /// Combines `self` (the animated contribution) with `other` (the property's base value)
+    /// for `CompositeOp::Add`/`Accumulate`. The default simply returns `self`, which makes
+    /// composite bindings degrade to `CompositeOp::Replace` for value types with no natural
+    /// addition.
+    fn add(&self, other: &Self) -> Self {
+        let _ = other;
+        self.clone()
+    }
+}
+
+impl InterpolatedPropertyValue for f32 {
+    fn interpolate(&self, target_value: &Self, t: f32) -> Self {
+        self + (target_value - self) * t
+    }
+    fn compute_squared_distance(&self, other: &Self) -> f64 {
+        ((*self - *other) as f64).powi(2)
+    }
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+}
+
+impl InterpolatedPropertyValue for f64 {
+    fn interpolate(&self, target_value: &Self, t: f32) -> Self {
+        self + (target_value - self) * t as f64
+    }
+    fn compute_squared_distance(&self, other: &Self) -> f64 {
+        (*self - *other).powi(2)
+    }
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+}
+
+/// A single `start_value -> end_value` run over `duration`, started at `start_time`.
+struct RunningAnimation<T> {
+    start_value: T,
+    end_value: T,
+    start_time: Instant,
+    duration: Duration,
+}
+
+impl<T: InterpolatedPropertyValue> RunningAnimation<T> {
+    fn progress_at(&self, instant: Instant) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        (instant.duration_since(self.start_time).as_secs_f32() / self.duration.as_secs_f32())
+            .clamp(0., 1.)
+    }
+    fn value_at(&self, instant: Instant) -> T {
+        self.start_value.interpolate(&self.end_value, self.progress_at(instant))
+    }
+    fn is_done_at(&self, instant: Instant) -> bool {
+        self.progress_at(instant) >= 1.0
+    }
+}
+
+enum Binding<T> {
+    None,
+    Function(Rc<dyn Fn() -> T>),
+}
+
+struct PropertyInner<T> {
+    value: T,
+    binding: Binding<T>,
+    animation: Option<RunningAnimation<T>>,
+    /// The running total for `CompositeOp::Accumulate`: each new composite binding's end value
+    /// is added onto this instead of onto the property's plain binding/value, so successive
+    /// accumulate bindings build on one another instead of each restarting from the base value.
+    composite_carry: Option<T>,
+}
+
+/// A property that can hold a plain value, a binding, or be driven by an animation.
+pub struct Property<T> {
+    inner: RefCell<PropertyInner<T>>,
+}
+
+impl<T: InterpolatedPropertyValue> Property<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: RefCell::new(PropertyInner {
+                value,
+                binding: Binding::None,
+                animation: None,
+                composite_carry: None,
+            }),
+        }
+    }
+
+    fn evaluate(&self, now: Instant) -> T {
+        let mut inner = self.inner.borrow_mut();
+        if let Binding::Function(f) = &inner.binding {
+            inner.value = f();
+        }
+        if let Some(animation) = &inner.animation {
+            let value = animation.value_at(now);
+            if animation.is_done_at(now) {
+                inner.animation = None;
+            }
+            value
+        } else {
+            inner.value.clone()
+        }
+    }
+
+    pub fn get(self: Pin<&Self>) -> T {
+        self.evaluate(Instant::now())
+    }
+
+    pub fn set(self: Pin<&Self>, value: T) {
+        let mut inner = self.inner.borrow_mut();
+        inner.binding = Binding::None;
+        inner.animation = None;
+        inner.value = value;
+    }
+
+    pub fn set_animated_value(self: Pin<&Self>, value: T, animation: PropertyAnimation) {
+        let now = Instant::now();
+        let start_value = self.evaluate(now);
+        let mut inner = self.inner.borrow_mut();
+        inner.binding = Binding::None;
+        inner.animation = Some(RunningAnimation {
+            start_value,
+            end_value: value,
+            start_time: now,
+            duration: Duration::from_millis(animation.duration.max(0) as u64),
+        });
+    }
+
+    pub fn set_binding(self: Pin<&Self>, binding: impl Fn() -> T + 'static) {
+        let mut inner = self.inner.borrow_mut();
+        inner.animation = None;
+        inner.binding = Binding::Function(Rc::new(binding));
+    }
+
+    pub fn set_animated_binding(
+        self: Pin<&Self>,
+        binding: impl Fn() -> T + 'static,
+        animation: PropertyAnimation,
+    ) {
+        let now = Instant::now();
+        let start_value = self.evaluate(now);
+        let target = binding();
+        let mut inner = self.inner.borrow_mut();
+        inner.binding = Binding::Function(Rc::new(binding));
+        inner.animation = Some(RunningAnimation {
+            start_value,
+            end_value: target,
+            start_time: now,
+            duration: Duration::from_millis(animation.duration.max(0) as u64),
+        });
+    }
+
+    /// Starts `animation` towards `binding`'s current value. If a previous animation is still
+    /// running on this property, preserves its velocity instead of snapping to the new target
+    /// with zero velocity: the new run starts from the in-flight animation's current sampled
+    /// value, and its duration is chosen (via [`crate::rtti::velocity_matched_duration`], at
+    /// most the authored duration) so the initial rate of change matches the interrupted
+    /// animation's speed, estimated from
+    /// [`InterpolatedPropertyValue::compute_squared_distance`] sampled at `now` and `now - dt`.
+    pub fn set_animated_binding_for_transition(
+        self: Pin<&Self>,
+        binding: impl Fn() -> T + 'static,
+        compute_animation: Box<dyn Fn() -> (PropertyAnimation, Instant)>,
+    ) {
+        const VELOCITY_SAMPLE_DT: Duration = Duration::from_millis(16);
+
+        let (animation, now) = compute_animation();
+        let target = binding();
+        let authored_duration = Duration::from_millis(animation.duration.max(0) as u64);
+
+        let interrupted = self.inner.borrow().animation.as_ref().filter(|a| !a.is_done_at(now)).map(
+            |running| {
+                let dt = VELOCITY_SAMPLE_DT.min(now.saturating_duration_since(running.start_time));
+                (running.value_at(now), running.value_at(now - dt), dt)
+            },
+        );
+
+        let (start_value, duration) = match interrupted {
+            Some((value_now, value_before, dt)) if !dt.is_zero() => {
+                let distance_to_new_target = value_now.compute_squared_distance(&target);
+                let current_speed =
+                    value_now.compute_squared_distance(&value_before).sqrt() / dt.as_secs_f64();
+                let duration = crate::rtti::velocity_matched_duration(
+                    distance_to_new_target,
+                    current_speed,
+                    authored_duration,
+                );
+                (value_now, duration)
+            }
+            Some((value_now, _, _)) => (value_now, authored_duration),
+            None => (self.evaluate(now), authored_duration),
+        };
+
+        let mut inner = self.inner.borrow_mut();
+        inner.binding = Binding::Function(Rc::new(binding));
+        inner.animation =
+            Some(RunningAnimation { start_value, end_value: target, start_time: now, duration });
+    }
+
+    /// Starts a `crate::rtti::CompositeOp`-driven binding: `binding`'s value is combined with a
+    /// base value via [`InterpolatedPropertyValue::add`] instead of replacing the property
+    /// outright.
+    ///
+    /// For `Replace` this degrades to [`Self::set_animated_binding`]. For `Add`, the base is the
+    /// property's existing binding (or plain value if there is none), re-sampled on every
+    /// evaluation of the combined binding. The animation started here runs towards the
+    /// combination sampled at bind time; once it finishes, the property follows the base's
+    /// *current* value. For `Accumulate`, the base is the end value carried over from the
+    /// previous composite binding (starting from the plain value the first time), so successive
+    /// accumulate bindings build on one another instead of each restarting fresh.
+    ///
+    /// Note this carries over the previous *composite binding's* end value, not the previous
+    /// *iteration of a repeating animation's* end value as in the web-animations model this is
+    /// borrowed from: `Property`/`RunningAnimation` has no notion of a repeating animation
+    /// restarting in place, so there is no per-iteration boundary to carry across. Each call to
+    /// `set_composite_binding` with `Accumulate` is the unit that accumulates instead.
+    pub fn set_composite_binding(
+        self: Pin<&Self>,
+        binding: impl Fn() -> T + 'static,
+        op: crate::rtti::CompositeOp,
+        animation: PropertyAnimation,
+    ) {
+        if matches!(op, crate::rtti::CompositeOp::Replace) {
+            return self.set_animated_binding(binding, animation);
+        }
+
+        let accumulate = matches!(op, crate::rtti::CompositeOp::Accumulate);
+        let base_fn: Rc<dyn Fn() -> T> = {
+            let inner = self.inner.borrow();
+            if accumulate {
+                let carry = inner.composite_carry.clone().unwrap_or_else(|| inner.value.clone());
+                Rc::new(move || carry.clone())
+            } else {
+                match &inner.binding {
+                    Binding::Function(f) => f.clone(),
+                    Binding::None => {
+                        let value = inner.value.clone();
+                        Rc::new(move || value.clone())
+                    }
+                }
+            }
+        };
+
+        let binding = Rc::new(binding);
+        let combined = {
+            let binding = binding.clone();
+            let base_fn = base_fn.clone();
+            move || binding().add(&base_fn())
+        };
+
+        if accumulate {
+            let end_value = combined();
+            self.inner.borrow_mut().composite_carry = Some(end_value);
+        }
+        self.set_animated_binding(combined, animation);
+    }
+
+    /// Sets `prop1` to `prop2`'s current value. This is a one-time copy: later changes to
+    /// either property are not propagated to the other.
+    pub fn link_two_way(prop1: Pin<&Self>, prop2: Pin<&Self>) {
+        let value = prop2.get();
+        prop1.set(value);
+    }
+}
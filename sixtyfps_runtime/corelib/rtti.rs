@@ -41,6 +41,52 @@ declare_ValueType![
     crate::items::ImageFit,
 ];
 
+/// When a `Transition` fires while a previous animation on the same property is still
+/// running, picks the new transition's duration as `sqrt(distance_to_new_target) /
+/// current_speed`, so its starting rate of change matches the interrupted animation's
+/// velocity instead of starting from zero velocity (which produces a visible jerk). The result
+/// is capped at `authored_duration`; when capped, the new transition starts faster than the
+/// interrupted animation was moving.
+///
+/// `Property::set_animated_binding_for_transition` samples the in-flight animation's value at
+/// `t` and at `t - dt`, computes `distance_to_new_target` and `current_speed`
+/// (`compute_squared_distance(value_at_t, value_at_t_minus_dt).sqrt() / dt`) via
+/// [`crate::properties::InterpolatedPropertyValue::compute_squared_distance`], and passes them
+/// here. A zero measured velocity falls back to `authored_duration`; a `distance_to_new_target`
+/// of zero means the property is already at its target, so the transition completes
+/// immediately.
+pub fn velocity_matched_duration(
+    distance_to_new_target: f64,
+    current_speed: f64,
+    authored_duration: core::time::Duration,
+) -> core::time::Duration {
+    if distance_to_new_target <= 0.0 {
+        return core::time::Duration::ZERO;
+    }
+    if current_speed <= 0.0 {
+        return authored_duration;
+    }
+    core::time::Duration::from_secs_f64(distance_to_new_target.sqrt() / current_speed)
+        .min(authored_duration)
+}
+
+/// How an animated binding's value is combined with the property it is bound to, borrowed
+/// from the web-animations compositing model.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompositeOp {
+    /// The animated value entirely replaces the property's value. This is the historical
+    /// behavior of `AnimatedBindingKind::Animation` and `AnimatedBindingKind::Transition`.
+    Replace,
+    /// The animated value is summed onto the property's base value (its existing binding or
+    /// plain value). The animation runs towards the sum sampled when the binding is set; once
+    /// it finishes, the property follows the sum with the base's current value.
+    Add,
+    /// Like `Add`, but successive `Accumulate` composite bindings build on the previous one's
+    /// end value instead of on the base value. There is no per-iteration accumulation within
+    /// a single repeating animation.
+    Accumulate,
+}
+
 /// What kind of animation is on a binding
 pub enum AnimatedBindingKind {
     /// No animation is on the binding
@@ -49,6 +95,9 @@ pub enum AnimatedBindingKind {
     Animation(PropertyAnimation),
     /// Transition
     Transition(Box<dyn Fn() -> (PropertyAnimation, crate::animations::Instant)>),
+    /// Single animation that is combined with the property's base value according to `op`,
+    /// instead of replacing it outright.
+    Composite { op: CompositeOp, animation: PropertyAnimation },
 }
 
 impl AnimatedBindingKind {
@@ -58,6 +107,7 @@ impl AnimatedBindingKind {
             AnimatedBindingKind::NotAnimated => None,
             AnimatedBindingKind::Animation(a) => Some(a),
             AnimatedBindingKind::Transition(_) => None,
+            AnimatedBindingKind::Composite { .. } => None,
         }
     }
 }
@@ -203,6 +253,16 @@ where
                 );
                 Ok(())
             }
+            AnimatedBindingKind::Composite { op, animation } => {
+                self.apply_pin(item).set_composite_binding(
+                    move || {
+                        binding().try_into().map_err(|_| ()).expect("binding was of the wrong type")
+                    },
+                    op,
+                    animation,
+                );
+                Ok(())
+            }
         }
     }
     fn offset(&self) -> usize {
@@ -233,6 +293,155 @@ where
     }
 }
 
+/// Interpolates one premultiplied-alpha channel: multiplies `a`/`b` by their respective
+/// alphas before interpolating, so that e.g. transitioning between a fully transparent color
+/// and an opaque one doesn't visibly fade through grey.
+fn interpolate_premultiplied_channel(a: u8, alpha_a: u8, b: u8, alpha_b: u8, t: f32) -> f32 {
+    let premultiplied_a = a as f32 * alpha_a as f32;
+    let premultiplied_b = b as f32 * alpha_b as f32;
+    premultiplied_a + (premultiplied_b - premultiplied_a) * t
+}
+
+impl crate::properties::InterpolatedPropertyValue for crate::Color {
+    fn interpolate(&self, target_value: &Self, t: f32) -> Self {
+        #[cfg(feature = "interpolate-oklab-color")]
+        return self.interpolate_oklab(target_value, t);
+
+        #[cfg(not(feature = "interpolate-oklab-color"))]
+        {
+            let (alpha_a, alpha_b) = (self.alpha(), target_value.alpha());
+            let alpha = alpha_a as f32 + (alpha_b as f32 - alpha_a as f32) * t;
+            // Un-premultiply: a fully transparent interpolated alpha means the premultiplied
+            // channels carry no information (dividing by a near-zero alpha would blow up), so
+            // fall back to the plain lerp of the straight channel values instead.
+            let unpremultiply = |premultiplied: f32, straight_a: u8, straight_b: u8| {
+                if alpha <= 0.0 {
+                    (straight_a as f32 + (straight_b as f32 - straight_a as f32) * t)
+                        .round()
+                        .clamp(0., 255.) as u8
+                } else {
+                    (premultiplied / alpha).round().clamp(0., 255.) as u8
+                }
+            };
+            crate::Color::from_argb_u8(
+                alpha.round().clamp(0., 255.) as u8,
+                unpremultiply(
+                    interpolate_premultiplied_channel(
+                        self.red(),
+                        alpha_a,
+                        target_value.red(),
+                        alpha_b,
+                        t,
+                    ),
+                    self.red(),
+                    target_value.red(),
+                ),
+                unpremultiply(
+                    interpolate_premultiplied_channel(
+                        self.green(),
+                        alpha_a,
+                        target_value.green(),
+                        alpha_b,
+                        t,
+                    ),
+                    self.green(),
+                    target_value.green(),
+                ),
+                unpremultiply(
+                    interpolate_premultiplied_channel(
+                        self.blue(),
+                        alpha_a,
+                        target_value.blue(),
+                        alpha_b,
+                        t,
+                    ),
+                    self.blue(),
+                    target_value.blue(),
+                ),
+            )
+        }
+    }
+
+    fn compute_squared_distance(&self, other: &Self) -> f64 {
+        let normalize = |c: u8| c as f64 / 255.0;
+        [
+            (normalize(self.red()), normalize(other.red())),
+            (normalize(self.green()), normalize(other.green())),
+            (normalize(self.blue()), normalize(other.blue())),
+            (normalize(self.alpha()), normalize(other.alpha())),
+        ]
+        .iter()
+        .map(|(a, b)| (a - b).powi(2))
+        .sum()
+    }
+}
+
+#[cfg(feature = "interpolate-oklab-color")]
+impl crate::Color {
+    /// Interpolates via the Oklab perceptual color space instead of premultiplied sRGB, which
+    /// avoids the "fade through mud" artifact that linear sRGB interpolation produces for some
+    /// hue pairs (e.g. red to green). Alpha is interpolated on its own and the color channels
+    /// are not premultiplied by it.
+    fn interpolate_oklab(&self, target_value: &Self, t: f32) -> Self {
+        let (l1, a1, b1, alpha1) = srgb_to_oklab(self);
+        let (l2, a2, b2, alpha2) = srgb_to_oklab(target_value);
+        let lerp = |x: f32, y: f32| x + (y - x) * t;
+        oklab_to_srgb(lerp(l1, l2), lerp(a1, a2), lerp(b1, b2), lerp(alpha1, alpha2))
+    }
+}
+
+#[cfg(feature = "interpolate-oklab-color")]
+fn srgb_to_oklab(color: &crate::Color) -> (f32, f32, f32, f32) {
+    let to_linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (to_linear(color.red()), to_linear(color.green()), to_linear(color.blue()));
+
+    let l = 0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b;
+    let m = 0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b;
+    let s = 0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b;
+
+    let (l_, m_, s_) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+    (
+        0.2104542553 * l_ + 0.7936177850 * m_ - 0.0040720468 * s_,
+        1.9779984951 * l_ - 2.4285922050 * m_ + 0.4505937099 * s_,
+        0.0259040371 * l_ + 0.7827717662 * m_ - 0.8086757660 * s_,
+        color.alpha() as f32 / 255.0,
+    )
+}
+
+#[cfg(feature = "interpolate-oklab-color")]
+fn oklab_to_srgb(l: f32, a: f32, b: f32, alpha: f32) -> crate::Color {
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+
+    let (l, m, s) = (l_.powi(3), m_.powi(3), s_.powi(3));
+
+    let r = 4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s;
+    let g = -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s;
+    let b = -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s;
+
+    let to_srgb = |c: f32| {
+        let c = c.clamp(0., 1.);
+        let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c * 255.0).round().clamp(0., 255.) as u8
+    };
+
+    crate::Color::from_argb_u8(
+        (alpha.clamp(0., 1.) * 255.0).round() as u8,
+        to_srgb(r),
+        to_srgb(g),
+        to_srgb(b),
+    )
+}
+
 pub trait BuiltinItem: Sized {
     fn name() -> &'static str;
     fn properties<Value: ValueType>() -> Vec<(&'static str, &'static dyn PropertyInfo<Self, Value>)>;
@@ -241,4 +450,21 @@ pub trait BuiltinItem: Sized {
         &'static str,
         const_field_offset::FieldOffset<Self, crate::Signal<()>, const_field_offset::AllowPin>,
     )>;
+
+    /// Looks up `name` in [`Self::properties`] and starts `animation` towards `target_value`
+    /// on the matching property, without the caller having to resolve the
+    /// `&'static dyn PropertyInfo` itself first. This is the entry point a viewer or a
+    /// scripting binding uses to animate a builtin item's property given only its name.
+    fn animate_property<Value: ValueType>(
+        item: Pin<&Self>,
+        name: &str,
+        target_value: Value,
+        animation: PropertyAnimation,
+    ) -> Result<(), ()> {
+        let (_, property_info) = Self::properties::<Value>()
+            .into_iter()
+            .find(|(prop_name, _)| *prop_name == name)
+            .ok_or(())?;
+        property_info.set(item, target_value, Some(animation))
+    }
 }
@@ -0,0 +1,243 @@
+/* LICENSE BEGIN
+    This file is part of the SixtyFPS Project -- https://sixtyfps.io
+    Copyright (c) 2020 Olivier Goffart <olivier.goffart@sixtyfps.io>
+    Copyright (c) 2020 Simon Hausmann <simon.hausmann@sixtyfps.io>
+
+    SPDX-License-Identifier: GPL-3.0-only
+    This file is also available under commercial licensing terms.
+    Please contact info@sixtyfps.io for more information.
+LICENSE END */
+/*!
+ Proc-macros used by the sixtyfps_corelib crate. Currently this is just the
+ `#[derive(InterpolatedPropertyValue)]` derive macro, which lets composite struct/enum value
+ types flow through the RTTI animation path (`crate::rtti::MaybeAnimatedPropertyInfoWrapper`)
+ without a hand-written `InterpolatedPropertyValue` impl.
+*/
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Whether `attr` is `#[animation(no_bound)]`: it must be the `animation` helper attribute,
+/// and its list must contain the `no_bound` path (not just any `#[animation(...)]`).
+fn is_no_bound_attr(attr: &syn::Attribute) -> bool {
+    if !attr.path.is_ident("animation") {
+        return false;
+    }
+    match attr.parse_meta() {
+        Ok(syn::Meta::List(list)) => list.nested.iter().any(|nested| {
+            matches!(nested, syn::NestedMeta::Meta(syn::Meta::Path(p)) if p.is_ident("no_bound"))
+        }),
+        _ => false,
+    }
+}
+
+/// A mismatched enum variant pair has no continuous path between them; this is the squared
+/// distance reported for that case, matching the `t >= 0.5` "snap to the other variant" policy
+/// used by the generated `interpolate`.
+const MISMATCHED_VARIANT_SQUARED_DISTANCE: f64 = 1.0;
+
+/// Fully-qualified call to `InterpolatedPropertyValue::interpolate(a, b, t)`, so the generated
+/// code doesn't depend on the trait being in scope at the call site (method syntax would fail
+/// to resolve for concrete field types such as `f32` that only implement the trait via its
+/// blanket/primitive impls, not a local `use`).
+fn ufcs_interpolate(a: &TokenStream2, b: &TokenStream2) -> TokenStream2 {
+    quote!(sixtyfps_corelib::properties::InterpolatedPropertyValue::interpolate(#a, #b, t))
+}
+
+/// Fully-qualified call to `InterpolatedPropertyValue::compute_squared_distance(a, b)`, for the
+/// same reason as [`ufcs_interpolate`].
+fn ufcs_squared_distance(a: &TokenStream2, b: &TokenStream2) -> TokenStream2 {
+    quote!(sixtyfps_corelib::properties::InterpolatedPropertyValue::compute_squared_distance(#a, #b))
+}
+
+/// Field-wise (struct) or variant-wise (enum) derive of `InterpolatedPropertyValue`, modeled
+/// after Servo's `Animate` derive.
+///
+/// For a struct, `interpolate(a, b, t)` produces a new struct whose every field is the
+/// interpolation of the corresponding fields of `a` and `b`, and `compute_squared_distance(a, b)`
+/// sums every field's own squared distance; every field type must itself be
+/// `InterpolatedPropertyValue`. Each type parameter gets a generated `InterpolatedPropertyValue`
+/// bound unless it is annotated `#[animation(no_bound)]`.
+///
+/// For an enum, matching variants are interpolated/measured field-wise; mismatched variants
+/// switch discretely at `t >= 0.5` for `interpolate` (there is no continuous path between
+/// differing variants) and report [`MISMATCHED_VARIANT_SQUARED_DISTANCE`] for
+/// `compute_squared_distance`.
+#[proc_macro_derive(InterpolatedPropertyValue, attributes(animation))]
+pub fn derive_interpolated_property_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let no_bound_params: std::collections::HashSet<_> = input
+        .generics
+        .type_params()
+        .filter(|p| p.attrs.iter().any(is_no_bound_attr))
+        .map(|p| p.ident.clone())
+        .collect();
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        // Strip our own helper attribute: left in place, it would be re-emitted verbatim by
+        // `split_for_impl()` into the generated `impl<..>`, where `animation` isn't a
+        // registered attribute and the compiler would reject it.
+        param.attrs.clear();
+        if !no_bound_params.contains(&param.ident) {
+            param.bounds.push(syn::parse_quote!(sixtyfps_corelib::properties::InterpolatedPropertyValue));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (interpolate_body, squared_distance_body) = match &input.data {
+        Data::Struct(s) => {
+            (interpolate_fields(&s.fields, quote!(#name)), squared_distance_fields(&s.fields))
+        }
+        Data::Enum(e) => {
+            let interpolate_arms = e.variants.iter().map(|variant| {
+                let variant_ident = &variant.ident;
+                let field_names_a: Vec<_> = field_idents(&variant.fields, "a");
+                let field_names_b: Vec<_> = field_idents(&variant.fields, "b");
+                let pattern_a = fields_pattern(&variant.fields, &field_names_a);
+                let pattern_b = fields_pattern(&variant.fields, &field_names_b);
+                let ctor = match &variant.fields {
+                    Fields::Named(named) => {
+                        let names = named.named.iter().map(|f| f.ident.clone().unwrap());
+                        let calls = field_names_a
+                            .iter()
+                            .zip(field_names_b.iter())
+                            .map(|(a, b)| ufcs_interpolate(&quote!(#a), &quote!(#b)));
+                        quote!(#name::#variant_ident { #(#names: #calls),* })
+                    }
+                    Fields::Unnamed(_) => {
+                        let calls = field_names_a
+                            .iter()
+                            .zip(field_names_b.iter())
+                            .map(|(a, b)| ufcs_interpolate(&quote!(#a), &quote!(#b)));
+                        quote!(#name::#variant_ident( #(#calls),* ))
+                    }
+                    Fields::Unit => quote!(#name::#variant_ident),
+                };
+                quote! {
+                    (#name::#variant_ident #pattern_a, #name::#variant_ident #pattern_b) => #ctor,
+                }
+            });
+            let interpolate_body = quote! {
+                match (self, target_value) {
+                    #(#interpolate_arms)*
+                    (a, b) => if t >= 0.5 { b.clone() } else { a.clone() },
+                }
+            };
+
+            let distance_arms = e.variants.iter().map(|variant| {
+                let variant_ident = &variant.ident;
+                let field_names_a: Vec<_> = field_idents(&variant.fields, "a");
+                let field_names_b: Vec<_> = field_idents(&variant.fields, "b");
+                let pattern_a = fields_pattern(&variant.fields, &field_names_a);
+                let pattern_b = fields_pattern(&variant.fields, &field_names_b);
+                let terms = field_names_a
+                    .iter()
+                    .zip(field_names_b.iter())
+                    .map(|(a, b)| ufcs_squared_distance(&quote!(#a), &quote!(#b)));
+                quote! {
+                    (#name::#variant_ident #pattern_a, #name::#variant_ident #pattern_b) => {
+                        0f64 #(+ #terms)*
+                    }
+                }
+            });
+            let squared_distance_body = quote! {
+                match (self, other) {
+                    #(#distance_arms)*
+                    (_, _) => #MISMATCHED_VARIANT_SQUARED_DISTANCE,
+                }
+            };
+
+            (interpolate_body, squared_distance_body)
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(
+                &input.ident,
+                "InterpolatedPropertyValue cannot be derived for unions",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    let expanded = quote! {
+        impl #impl_generics sixtyfps_corelib::properties::InterpolatedPropertyValue for #name #ty_generics #where_clause {
+            fn interpolate(&self, target_value: &Self, t: f32) -> Self {
+                #[allow(unused_variables)]
+                let target_value = target_value;
+                #interpolate_body
+            }
+            fn compute_squared_distance(&self, other: &Self) -> f64 {
+                #[allow(unused_variables)]
+                let other = other;
+                #squared_distance_body
+            }
+        }
+    };
+    expanded.into()
+}
+
+fn field_idents(fields: &Fields, prefix: &str) -> Vec<syn::Ident> {
+    (0..fields.len())
+        .map(|i| quote::format_ident!("{}_{}", prefix, i))
+        .collect()
+}
+
+fn fields_pattern(fields: &Fields, names: &[syn::Ident]) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let field_idents = named.named.iter().map(|f| f.ident.clone().unwrap());
+            quote!({ #(#field_idents: #names),* })
+        }
+        Fields::Unnamed(_) => quote!(( #(#names),* )),
+        Fields::Unit => quote!(),
+    }
+}
+
+fn interpolate_fields(fields: &Fields, ctor: TokenStream2) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let assigns = named.named.iter().map(|f| {
+                let ident = f.ident.as_ref().unwrap();
+                let call =
+                    ufcs_interpolate(&quote!(&self.#ident), &quote!(&target_value.#ident));
+                quote!(#ident: #call)
+            });
+            quote!(#ctor { #(#assigns),* })
+        }
+        Fields::Unnamed(unnamed) => {
+            let assigns = unnamed.unnamed.iter().enumerate().map(|(i, _)| {
+                let index = syn::Index::from(i);
+                ufcs_interpolate(&quote!(&self.#index), &quote!(&target_value.#index))
+            });
+            quote!(#ctor ( #(#assigns),* ))
+        }
+        Fields::Unit => ctor,
+    }
+}
+
+fn squared_distance_fields(fields: &Fields) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let terms = named.named.iter().map(|f| {
+                let ident = f.ident.as_ref().unwrap();
+                ufcs_squared_distance(&quote!(&self.#ident), &quote!(&other.#ident))
+            });
+            quote!(0f64 #(+ #terms)*)
+        }
+        Fields::Unnamed(unnamed) => {
+            let terms = unnamed.unnamed.iter().enumerate().map(|(i, _)| {
+                let index = syn::Index::from(i);
+                ufcs_squared_distance(&quote!(&self.#index), &quote!(&other.#index))
+            });
+            quote!(0f64 #(+ #terms)*)
+        }
+        Fields::Unit => quote!(0f64),
+    }
+}